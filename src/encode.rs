@@ -0,0 +1,230 @@
+use std::fmt::Write as FmtWrite;
+use std::io::{self, Write as IoWrite};
+
+use super::Value;
+
+/// Serializes `value` to a compact JSON string, with no extra whitespace.
+pub fn encode(value: &Value) -> String {
+    let mut s = String::new();
+    Encoder::new(&mut s).encode_value(value);
+    s
+}
+
+/// Serializes `value` to an indented, human-readable JSON string, using two
+/// spaces per indentation level.
+pub fn encode_pretty(value: &Value) -> String {
+    encode_pretty_with_indent(value, 2)
+}
+
+/// Like `encode_pretty`, but with a configurable number of spaces per
+/// indentation level.
+pub fn encode_pretty_with_indent(value: &Value, indent: usize) -> String {
+    let mut s = String::new();
+    PrettyEncoder::with_indent(&mut s, indent).encode_value(value);
+    s
+}
+
+/// Writes the compact encoding of `value` to `writer`.
+pub fn write_to<W: IoWrite>(value: &Value, writer: &mut W) -> io::Result<()> {
+    writer.write_all(encode(value).as_bytes())
+}
+
+/// Writes the pretty-printed encoding of `value` to `writer`.
+pub fn write_pretty_to<W: IoWrite>(value: &Value, writer: &mut W) -> io::Result<()> {
+    writer.write_all(encode_pretty(value).as_bytes())
+}
+
+/// Compact JSON encoder: emits tokens back to back with no whitespace.
+pub struct Encoder<'a> {
+    writer: &'a mut String,
+}
+
+impl<'a> Encoder<'a> {
+    pub fn new(writer: &'a mut String) -> Encoder<'a> {
+        Encoder { writer: writer }
+    }
+
+    pub fn encode_value(&mut self, value: &Value) {
+        match *value {
+            Value::Null => self.writer.push_str("null"),
+            Value::Bool(true) => self.writer.push_str("true"),
+            Value::Bool(false) => self.writer.push_str("false"),
+            Value::Int(n) => { write!(self.writer, "{}", n).unwrap(); },
+            Value::Uint(n) => { write!(self.writer, "{}", n).unwrap(); },
+            Value::Float(f) => push_float(f, self.writer),
+            Value::String(ref s) => escape_str(s, self.writer),
+            Value::List(ref list) => {
+                self.writer.push('[');
+                for (i, v) in list.iter().enumerate() {
+                    if i > 0 {
+                        self.writer.push(',');
+                    }
+                    self.encode_value(v);
+                }
+                self.writer.push(']');
+            },
+            Value::Object(ref obj) => {
+                self.writer.push('{');
+                for (i, (k, v)) in obj.iter().enumerate() {
+                    if i > 0 {
+                        self.writer.push(',');
+                    }
+                    escape_str(k, self.writer);
+                    self.writer.push(':');
+                    self.encode_value(v);
+                }
+                self.writer.push('}');
+            },
+        }
+    }
+}
+
+/// Pretty-printing JSON encoder with configurable indentation.
+pub struct PrettyEncoder<'a> {
+    writer: &'a mut String,
+    indent: usize,
+    curr_indent: usize,
+}
+
+impl<'a> PrettyEncoder<'a> {
+    pub fn new(writer: &'a mut String) -> PrettyEncoder<'a> {
+        PrettyEncoder::with_indent(writer, 2)
+    }
+
+    pub fn with_indent(writer: &'a mut String, indent: usize) -> PrettyEncoder<'a> {
+        PrettyEncoder { writer: writer, indent: indent, curr_indent: 0 }
+    }
+
+    fn newline_indent(&mut self) {
+        self.writer.push('\n');
+        for _ in 0..self.curr_indent {
+            self.writer.push(' ');
+        }
+    }
+
+    pub fn encode_value(&mut self, value: &Value) {
+        match *value {
+            Value::Null => self.writer.push_str("null"),
+            Value::Bool(true) => self.writer.push_str("true"),
+            Value::Bool(false) => self.writer.push_str("false"),
+            Value::Int(n) => { write!(self.writer, "{}", n).unwrap(); },
+            Value::Uint(n) => { write!(self.writer, "{}", n).unwrap(); },
+            Value::Float(f) => push_float(f, self.writer),
+            Value::String(ref s) => escape_str(s, self.writer),
+            Value::List(ref list) => {
+                if list.is_empty() {
+                    self.writer.push_str("[]");
+                    return;
+                }
+                self.writer.push('[');
+                self.curr_indent += self.indent;
+                for (i, v) in list.iter().enumerate() {
+                    if i > 0 {
+                        self.writer.push(',');
+                    }
+                    self.newline_indent();
+                    self.encode_value(v);
+                }
+                self.curr_indent -= self.indent;
+                self.newline_indent();
+                self.writer.push(']');
+            },
+            Value::Object(ref obj) => {
+                if obj.is_empty() {
+                    self.writer.push_str("{}");
+                    return;
+                }
+                self.writer.push('{');
+                self.curr_indent += self.indent;
+                for (i, (k, v)) in obj.iter().enumerate() {
+                    if i > 0 {
+                        self.writer.push(',');
+                    }
+                    self.newline_indent();
+                    escape_str(k, self.writer);
+                    self.writer.push_str(": ");
+                    self.encode_value(v);
+                }
+                self.curr_indent -= self.indent;
+                self.newline_indent();
+                self.writer.push('}');
+            },
+        }
+    }
+}
+
+// Formats a float the way `{}` does, but appends `.0` when the result would
+// otherwise look like an integer, so that re-parsing the output yields a
+// `Value::Float` rather than a `Value::Int`.
+//
+// NaN and +/-Infinity have no JSON representation; `{}` would print them as
+// `NaN`/`inf`/`-inf`, which aren't valid JSON tokens and wouldn't reparse, so
+// those are encoded as `null` instead.
+fn push_float(f: f64, out: &mut String) {
+    if !f.is_finite() {
+        out.push_str("null");
+        return;
+    }
+    let start = out.len();
+    write!(out, "{}", f).unwrap();
+    let repr = &out[start..];
+    if !repr.contains('.') {
+        out.push_str(".0");
+    }
+}
+
+fn escape_str(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\u{08}' => out.push_str("\\b"),
+            '\u{0c}' => out.push_str("\\f"),
+            c if (c as u32) < 0x20 => { write!(out, "\\u{:04x}", c as u32).unwrap(); },
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Value;
+    use super::encode;
+
+    #[test]
+    fn escapes_quotes_backslashes_and_named_control_chars() {
+        let s = Value::String("a\"b\\c\nd\re\tf\u{08}g\u{0c}h".to_string());
+        assert_eq!(encode(&s), r#""a\"b\\c\nd\re\tf\bg\fh""#);
+    }
+
+    #[test]
+    fn escapes_other_control_chars_as_unicode_escapes() {
+        let s = Value::String("\u{0}\u{1}\u{1f}".to_string());
+        assert_eq!(encode(&s), "\"\\u0000\\u0001\\u001f\"");
+    }
+
+    #[test]
+    fn appends_point_zero_to_integral_floats_so_they_round_trip() {
+        assert_eq!(encode(&Value::Float(1.0)), "1.0");
+        assert_eq!(encode(&Value::Float(-2.0)), "-2.0");
+        assert_eq!(encode(&Value::Float(1.5)), "1.5");
+    }
+
+    #[test]
+    fn encodes_non_finite_floats_as_null() {
+        assert_eq!(encode(&Value::Float(::std::f64::NAN)), "null");
+        assert_eq!(encode(&Value::Float(::std::f64::INFINITY)), "null");
+        assert_eq!(encode(&Value::Float(::std::f64::NEG_INFINITY)), "null");
+    }
+
+    #[test]
+    fn compact_encoding_has_no_extra_whitespace() {
+        let list = Value::List(vec![Value::Int(1), Value::Bool(true), Value::Null]);
+        assert_eq!(encode(&list), "[1,true,null]");
+    }
+}
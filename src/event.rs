@@ -0,0 +1,463 @@
+use std::char;
+use std::cmp::min;
+use std::collections::VecDeque;
+use std::io::{self, Read};
+use std::mem;
+
+use super::{ErrorCode, JsonError, Position};
+use tables::{STATES, GOTOS, CATCODE};
+
+// Appends `c`'s UTF-8 encoding to the raw byte buffer used for in-progress
+// string literals.
+fn push_char(buf: &mut Vec<u8>, c: char) {
+    let mut enc = [0u8; 4];
+    let s = c.encode_utf8(&mut enc);
+    buf.extend_from_slice(s.as_bytes());
+}
+
+/// One token of a streaming, SAX-style JSON parse.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonEvent {
+    ObjectStart,
+    ObjectKey(String),
+    ObjectEnd,
+    ListStart,
+    ListEnd,
+    Null,
+    Bool(bool),
+    Int(i64),
+    Uint(u64),
+    Float(f64),
+    String(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Container {
+    List,
+    Object,
+}
+
+/// A pull parser that yields `JsonEvent`s as it reads, without ever
+/// materializing a full `Value` tree.
+///
+/// It drives the `STATES`/`GOTOS`/`CATCODE` grammar tables directly, but
+/// instead of pushing values onto a data stack, it queues an event every time a
+/// value-producing action fires. This lets callers walk documents too large
+/// to hold in memory as a single `Value`.
+pub struct EventParser<R> {
+    bytes: io::Bytes<R>,
+    state: u8,
+    stack: Vec<u8>,
+    containers: Vec<Container>,
+    expect_key: Vec<bool>,
+    expect_colon: bool,
+    expect_value: bool,
+    in_string: bool,
+    ss: Vec<u8>,
+    es: String,
+    high_surrogate: Option<u16>,
+    pending: VecDeque<JsonEvent>,
+    eof_sent: bool,
+    done: bool,
+    offset: usize,
+    line: usize,
+    col: usize,
+}
+
+impl<R: Read> EventParser<R> {
+    pub fn new(input: R) -> EventParser<R> {
+        EventParser {
+            bytes: input.bytes(),
+            state: 0,
+            stack: vec![],
+            containers: vec![],
+            expect_key: vec![],
+            expect_colon: false,
+            expect_value: true,
+            in_string: false,
+            ss: Vec::new(),
+            es: String::new(),
+            high_surrogate: None,
+            pending: VecDeque::new(),
+            eof_sent: false,
+            done: false,
+            offset: 0,
+            line: 1,
+            col: 1,
+        }
+    }
+
+    fn push_scalar(&mut self, ev: JsonEvent) {
+        self.expect_value = false;
+        self.pending.push_back(ev);
+    }
+
+    // Named to avoid colliding with `Iterator::position`, which method
+    // lookup would otherwise prefer over this inherent method from inside
+    // any `&mut self` method on `EventParser`.
+    fn cur_position(&self) -> Position {
+        Position { offset: self.offset, line: self.line, col: self.col }
+    }
+
+    // Advances the position counters past `ch`, which has just been fed in.
+    fn advance(&mut self, ch: u8) {
+        self.offset += 1;
+        if ch == b'\n' {
+            self.line += 1;
+            self.col = 1;
+        } else {
+            self.col += 1;
+        }
+    }
+
+    // Guesses which specific dead end the grammar hit, based on what we were
+    // expecting to see next. We don't have a distinct error code per `STATES`
+    // transition, but the context we already track (current container,
+    // whether we're mid-key, awaiting a colon, or awaiting a value) covers
+    // the common cases.
+    fn syntax_error_code(&self) -> ErrorCode {
+        if self.containers.last() == Some(&Container::Object) &&
+                self.expect_key.last() == Some(&true) {
+            return ErrorCode::KeyMustBeString;
+        }
+        if self.expect_colon {
+            return ErrorCode::ExpectedColon;
+        }
+        if self.expect_value {
+            return ErrorCode::ValueExpected;
+        }
+        if !self.containers.is_empty() {
+            return ErrorCode::ExpectedCommaOrClose;
+        }
+        ErrorCode::UnexpectedTrailingCharacter
+    }
+
+    fn feed(&mut self, ch: u8) -> Result<(), JsonError> {
+        let cat = CATCODE[min(ch, 0x7e) as usize];
+        self.feed_cat(cat, ch, true)
+    }
+
+    // Like `feed`, but lets the caller supply the grammar category directly
+    // instead of deriving it from `ch`, and whether to advance the position
+    // counters at all. Used by the EOF flush below, which needs to push a
+    // byte through the state machine that *acts* like whitespace (to flush a
+    // trailing number and let a just-completed top-level value reduce back
+    // to state 0) without actually being a valid whitespace byte or a real
+    // byte of input -- so it must leave `offset`/`line`/`col` right where
+    // real input ended, for an accurate `Truncated` position on failure.
+    fn feed_cat(&mut self, cat: u8, ch: u8, advance_pos: bool) -> Result<(), JsonError> {
+        let pos = self.cur_position();
+        if advance_pos {
+            self.advance(ch);
+        }
+        loop {
+            let mut code: u16 = STATES[self.state as usize][cat as usize];
+            let mut action: u8 = (code >> 8 & 0xFF) as u8;
+            code = code & 0xFF;
+            if action == 0xFF && code == 0xFF {
+                return Err(JsonError::Syntax(self.syntax_error_code(), pos));
+            } else if action >= 0x80 {
+                self.stack.push(GOTOS[self.state as usize]);
+                action -= 0x80;
+            }
+            if action > 0 {
+                try!(self.do_action(action, ch, pos));
+            }
+            if code == 0xFF {
+                self.state = self.stack.pop().unwrap();
+            } else {
+                self.state = code as u8;
+                break;
+            }
+        }
+        if !self.in_string {
+            match ch {
+                b':' if self.expect_colon => {
+                    self.expect_colon = false;
+                    self.expect_value = true;
+                },
+                b',' if self.containers.last() == Some(&Container::List) => {
+                    self.expect_value = true;
+                },
+                b']' if self.containers.last() == Some(&Container::List) => {
+                    self.containers.pop();
+                    self.expect_value = false; // the list itself just completed as a value
+                    self.pending.push_back(JsonEvent::ListEnd);
+                },
+                b'}' if self.containers.last() == Some(&Container::Object) => {
+                    self.containers.pop();
+                    self.expect_key.pop();
+                    self.expect_value = false; // the object itself just completed as a value
+                    self.pending.push_back(JsonEvent::ObjectEnd);
+                },
+                _ => {},
+            }
+        }
+        Ok(())
+    }
+
+    fn do_action(&mut self, action: u8, ch: u8, pos: Position) -> Result<(), JsonError> {
+        match action {
+            0x1 => { // push list
+                self.containers.push(Container::List);
+                self.expect_value = true; // awaiting the first element, or `]`
+                self.pending.push_back(JsonEvent::ListStart);
+            },
+            0x2 => { // push object
+                self.containers.push(Container::Object);
+                self.expect_key.push(true);
+                self.expect_value = false; // awaiting a key, or `}`, not a value
+                self.pending.push_back(JsonEvent::ObjectStart);
+            },
+            0x3 => {}, // pop & append: no event of its own, nothing to track
+            0x4 => { // pop pop & setitem: entry finished, back to expecting a key
+                if let Some(top) = self.expect_key.last_mut() {
+                    *top = true;
+                }
+            },
+            0x5 => { self.push_scalar(JsonEvent::Null); },
+            0x6 => { self.push_scalar(JsonEvent::Bool(true)); },
+            0x7 => { self.push_scalar(JsonEvent::Bool(false)); },
+            0x8 => { // push string
+                if self.high_surrogate.take().is_some() {
+                    return Err(JsonError::InvalidEscape(
+                            "unpaired high surrogate".to_string(), pos));
+                }
+                self.in_string = false;
+                let bytes = mem::replace(&mut self.ss, Vec::new());
+                let s = try!(String::from_utf8(bytes).map_err(JsonError::InvalidUtf8));
+                self.es.clear();
+                if self.containers.last() == Some(&Container::Object) &&
+                        self.expect_key.last() == Some(&true) {
+                    *self.expect_key.last_mut().unwrap() = false;
+                    self.expect_colon = true;
+                    self.pending.push_back(JsonEvent::ObjectKey(s));
+                } else {
+                    self.push_scalar(JsonEvent::String(s));
+                }
+            },
+            0x9 => { // push int: try i64, then u64, before giving up
+                self.in_string = false;
+                let bytes = mem::replace(&mut self.ss, Vec::new());
+                let s = String::from_utf8(bytes).unwrap();
+                if let Ok(n) = s.parse::<i64>() {
+                    self.push_scalar(JsonEvent::Int(n));
+                } else if let Ok(n) = s.parse::<u64>() {
+                    self.push_scalar(JsonEvent::Uint(n));
+                } else {
+                    return Err(JsonError::NumberOutOfRange(pos));
+                }
+            },
+            0xA => { // push float
+                self.in_string = false;
+                let bytes = mem::replace(&mut self.ss, Vec::new());
+                let s = String::from_utf8(bytes).unwrap();
+                let f = try!(s.parse().map_err(|_| JsonError::NumberOutOfRange(pos)));
+                self.push_scalar(JsonEvent::Float(f));
+            },
+            0xB => { // push raw byte to ss
+                if self.high_surrogate.take().is_some() {
+                    return Err(JsonError::InvalidEscape(
+                            "high surrogate not followed by its low surrogate".to_string(), pos));
+                }
+                self.in_string = true;
+                self.ss.push(ch);
+            },
+            0xC => { // push ch to es
+                self.in_string = true;
+                self.es.push(ch as char);
+            },
+            0xD => { // push escape
+                if self.high_surrogate.take().is_some() {
+                    return Err(JsonError::InvalidEscape(
+                            "high surrogate not followed by its low surrogate".to_string(), pos));
+                }
+                self.in_string = true;
+                let c: u8 = match ch as char {
+                    'b' => 8,
+                    't' => 9,
+                    'n' => 10,
+                    'f' => 12,
+                    'r' => 13,
+                    _ => { return Err(JsonError::InvalidEscape(format!("\\{}", ch), pos)); },
+                };
+                self.ss.push(c);
+                self.es.clear();
+            },
+            0xE => { // push unicode code point
+                self.in_string = true;
+                let n = try!(u16::from_str_radix(&self.es, 16).map_err(|_|
+                        JsonError::InvalidEscape(format!("\\u{}", self.es), pos)));
+                self.es.clear();
+                match n {
+                    0xD800...0xDBFF => { // high surrogate: stash it, next \u must be its pair
+                        if self.high_surrogate.is_some() {
+                            return Err(JsonError::InvalidEscape(
+                                    "unpaired high surrogate".to_string(), pos));
+                        }
+                        self.high_surrogate = Some(n);
+                    },
+                    0xDC00...0xDFFF => { // low surrogate: must follow a stashed high surrogate
+                        let high = try!(self.high_surrogate.take().ok_or_else(||
+                                JsonError::InvalidEscape("unpaired low surrogate".to_string(), pos)));
+                        let c = 0x10000 + ((high as u32 - 0xD800) << 10) + (n as u32 - 0xDC00);
+                        push_char(&mut self.ss, char::from_u32(c).unwrap());
+                    },
+                    _ => {
+                        if self.high_surrogate.take().is_some() {
+                            return Err(JsonError::InvalidEscape(
+                                    "high surrogate not followed by its low surrogate".to_string(),
+                                    pos));
+                        }
+                        match char::from_u32(n as u32) {
+                            Some(u) => push_char(&mut self.ss, u),
+                            None => return Err(JsonError::InvalidEscape(
+                                    format!("\\u{:04x}", n), pos)),
+                        }
+                    },
+                }
+            },
+            _ => panic!("JSON decoder bug"),
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Iterator for EventParser<R> {
+    type Item = Result<JsonEvent, JsonError>;
+
+    fn next(&mut self) -> Option<Result<JsonEvent, JsonError>> {
+        loop {
+            if let Some(ev) = self.pending.pop_front() {
+                return Some(Ok(ev));
+            }
+            if self.done {
+                return None;
+            }
+            match self.bytes.next() {
+                Some(Ok(ch)) => {
+                    if let Err(e) = self.feed(ch) {
+                        self.done = true;
+                        return Some(Err(e));
+                    }
+                },
+                Some(Err(e)) => {
+                    self.done = true;
+                    return Some(Err(JsonError::IO(e)));
+                },
+                None => {
+                    if self.eof_sent {
+                        self.done = true;
+                        if self.state != 0 {
+                            return Some(Err(JsonError::Truncated(self.cur_position())));
+                        }
+                        return None;
+                    }
+                    self.eof_sent = true;
+                    // Feed a synthetic byte, categorized as whitespace, so
+                    // any trailing number stuck in `ss` gets flushed and a
+                    // just-completed top-level value (or empty input) can
+                    // reduce back to state 0. It must carry whitespace's
+                    // *category*, not its own -- '?' is not whitespace and
+                    // would otherwise look like trailing garbage after
+                    // every successfully-parsed document. It also must not
+                    // advance the position counters: it isn't a real byte of
+                    // input, and a `Truncated` error below should point at
+                    // the actual end of input.
+                    if let Err(e) = self.feed_cat(CATCODE[32], b'?', false) {
+                        self.done = true;
+                        return Some(Err(e));
+                    }
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{parse, JsonError, Value};
+
+    #[test]
+    fn trailing_whitespace_free_values_parse() {
+        match parse(&b"1"[..]).unwrap() {
+            Value::Int(1) => {},
+            other => panic!("expected Int(1), got {:?}", other),
+        }
+        match parse(&b"null"[..]).unwrap() {
+            Value::Null => {},
+            other => panic!("expected Null, got {:?}", other),
+        }
+        match parse(&b"{}"[..]).unwrap() {
+            Value::Object(ref o) => assert!(o.is_empty()),
+            other => panic!("expected empty Object, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn empty_input_yields_no_objects() {
+        match parse(&b""[..]) {
+            Err(JsonError::NoObjects) => {},
+            other => panic!("expected NoObjects, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn decodes_valid_surrogate_pair() {
+        // \uD83D\uDE00 is the UTF-16 surrogate pair for U+1F600 (GRINNING FACE).
+        let input = r#""\uD83D\uDE00""#;
+        match parse(input.as_bytes()).unwrap() {
+            Value::String(ref s) => assert_eq!(s, "\u{1F600}"),
+            other => panic!("expected String, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_unpaired_high_surrogate() {
+        match parse(&br#""\uD83D""#[..]) {
+            Err(JsonError::InvalidEscape(..)) => {},
+            other => panic!("expected InvalidEscape, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_unpaired_low_surrogate() {
+        match parse(&br#""\uDE00""#[..]) {
+            Err(JsonError::InvalidEscape(..)) => {},
+            other => panic!("expected InvalidEscape, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn preserves_multi_byte_utf8_in_strings() {
+        // Mixes 2-, 3-, and 4-byte UTF-8 sequences, all with continuation
+        // bytes above 0x7e, to exercise the CATCODE lookup's clamp.
+        let text = "caf\u{e9} \u{65e5}\u{672c} \u{1F389}";
+        let mut input = Vec::new();
+        input.push(b'"');
+        input.extend_from_slice(text.as_bytes());
+        input.push(b'"');
+        match parse(&input[..]).unwrap() {
+            Value::String(ref s) => assert_eq!(s, text),
+            other => panic!("expected String, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_u64_only_integer_as_uint() {
+        // One past i64::MAX, so it only fits in a u64.
+        match parse(&b"9223372036854775808"[..]).unwrap() {
+            Value::Uint(9223372036854775808) => {},
+            other => panic!("expected Uint(9223372036854775808), got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_integer_too_large_for_u64() {
+        // One past u64::MAX.
+        match parse(&b"18446744073709551616"[..]) {
+            Err(JsonError::NumberOutOfRange(..)) => {},
+            other => panic!("expected NumberOutOfRange, got {:?}", other),
+        }
+    }
+}
@@ -1,18 +1,46 @@
-use std::char;
 use std::collections::BTreeMap;
-use std::cmp::min;
 use std::io::{self, Read};
+use std::string::FromUtf8Error;
 
 mod tables;
-use tables::{STATES, GOTOS, CATCODE};
+
+mod encode;
+pub use encode::{encode, encode_pretty, encode_pretty_with_indent, write_to, write_pretty_to,
+                  Encoder, PrettyEncoder};
+
+mod event;
+pub use event::{JsonEvent, EventParser};
+
+mod tojson;
+pub use tojson::ToJson;
+
+/// A location in the input, for pinpointing where a parse error occurred.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Position {
+    pub offset: usize,
+    pub line: usize,
+    pub col: usize,
+}
+
+/// What kind of dead end the parser hit, for `JsonError::Syntax`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorCode {
+    ExpectedColon,
+    ExpectedCommaOrClose,
+    KeyMustBeString,
+    ValueExpected,
+    UnexpectedTrailingCharacter,
+}
 
 #[derive(Debug)]
 pub enum JsonError {
-    Truncated,
+    Truncated(Position),
     NoObjects,
     MultipleObjects,
-    Syntax,
-    InvalidEscape(String),
+    Syntax(ErrorCode, Position),
+    InvalidEscape(String, Position),
+    InvalidUtf8(FromUtf8Error),
+    NumberOutOfRange(Position),
     IO(io::Error),
 }
 
@@ -21,153 +49,79 @@ pub enum Value {
     Null,
     Bool(bool),
     Int(i64),
+    Uint(u64),
     Float(f64),
     String(String),
     List(Vec<Value>),
     Object(BTreeMap<String, Value>),
 }
 
-impl Value {
-    fn into_string(self) -> String {
-        match self {
-            Value::String(s) => s,
-            _ => panic!("wrong type - expected String, got {:?}", self),
-        }
-    }
-
-    fn as_list(&mut self) -> &mut Vec<Value> {
-        match self {
-            &mut Value::List(ref mut l) => l,
-            _ => panic!("wrong type - expected List, got {:?}", self),
-        }
-    }
+/// Parses a complete JSON document into a single `Value` tree.
+///
+/// This is expressed on top of `EventParser`: it just replays the event
+/// stream into nested `List`/`Object` values, building the tree as the
+/// events arrive. Use `EventParser` directly if the whole tree doesn't need
+/// to fit in memory at once.
+pub fn parse<R: Read>(input: R) -> Result<Value, JsonError> {
+    let mut stack: Vec<Value> = vec![];
+    let mut keys: Vec<Option<String>> = vec![];
+    let mut result: Option<Value> = None;
 
-    fn as_object(&mut self) -> &mut BTreeMap<String, Value> {
-        match self {
-            &mut Value::Object(ref mut o) => o,
-            _ => panic!("wrong type - expected Object, got {:?}", self),
+    for event in EventParser::new(input) {
+        match try!(event) {
+            JsonEvent::ObjectStart => {
+                stack.push(Value::Object(BTreeMap::new()));
+                keys.push(None);
+            },
+            JsonEvent::ObjectKey(k) => {
+                *keys.last_mut().unwrap() = Some(k);
+            },
+            JsonEvent::ObjectEnd => {
+                keys.pop();
+                let v = stack.pop().unwrap();
+                try!(complete_value(&mut stack, &mut keys, v, &mut result));
+            },
+            JsonEvent::ListStart => {
+                stack.push(Value::List(vec![]));
+            },
+            JsonEvent::ListEnd => {
+                let v = stack.pop().unwrap();
+                try!(complete_value(&mut stack, &mut keys, v, &mut result));
+            },
+            JsonEvent::Null => try!(complete_value(&mut stack, &mut keys, Value::Null, &mut result)),
+            JsonEvent::Bool(b) => try!(complete_value(&mut stack, &mut keys, Value::Bool(b), &mut result)),
+            JsonEvent::Int(n) => try!(complete_value(&mut stack, &mut keys, Value::Int(n), &mut result)),
+            JsonEvent::Uint(n) => try!(complete_value(&mut stack, &mut keys, Value::Uint(n), &mut result)),
+            JsonEvent::Float(f) => try!(complete_value(&mut stack, &mut keys, Value::Float(f), &mut result)),
+            JsonEvent::String(s) => try!(complete_value(&mut stack, &mut keys, Value::String(s), &mut result)),
         }
     }
-}
-
-pub fn parse<R: Read>(input: R) -> Result<Value, JsonError> {
-    let mut stack = vec![];
-    let mut state = 0;
-    let mut ds: Vec<Value> = vec![];    // data stack
-    let mut ss = String::new();         // string stack
-    let mut es = String::new();         // escape stack
-    for maybe_ch in input.bytes() {
-        let ch = try!(maybe_ch.map_err(JsonError::IO));
-        let cat = CATCODE[min(ch, 0x7e) as usize];
-        state = try!(parse_ch(cat, ch, &mut stack, state, &mut ds,
-                              &mut ss, &mut es));
-    }
-    state = try!(parse_ch(CATCODE[32], '?' as u8, &mut stack, state,
-                          &mut ds, &mut ss, &mut es));
-    if state != 0 {
-        return Err(JsonError::Truncated);
-    }
-    match ds.len() {
-        0 => Err(JsonError::NoObjects),
-        1 => Ok(ds.pop().unwrap()),
-        _ => Err(JsonError::MultipleObjects),
-    }
-}
 
-fn parse_ch(cat: u8, ch: u8, stack: &mut Vec<u8>, mut state: u8,
-            ds: &mut Vec<Value>, ss: &mut String, es: &mut String)
-        -> Result<u8, JsonError> {
-    loop {
-        let mut code: u16 = STATES[state as usize][cat as usize];
-        let mut action: u8 = (code >> 8 & 0xFF) as u8;
-        code = code & 0xFF;
-        if action == 0xFF && code == 0xFF {
-            return Err(JsonError::Syntax);
-        } else if action >= 0x80 {
-            stack.push(GOTOS[state as usize]);
-            action -= 0x80;
-        }
-        if action > 0 {
-            try!(do_action(action, ch, ds, ss, es));
-        }
-        if code == 0xFF {
-            state = stack.pop().unwrap();
-        } else {
-            state = code as u8;
-            return Ok(state);
-        }
+    match result {
+        Some(v) => Ok(v),
+        None => Err(JsonError::NoObjects),
     }
 }
 
-fn do_action(action: u8, ch: u8, ds: &mut Vec<Value>, ss: &mut String,
-             es: &mut String) -> Result<(), JsonError> {
-    match action {
-        0x1 => { // push list
-            ds.push(Value::List(vec![]));
-        },
-        0x2 => { // push object
-            ds.push(Value::Object(BTreeMap::new()));
-        },
-        0x3 => { // pop & append
-            let v = ds.pop().unwrap();
-            ds.last_mut().unwrap().as_list().push(v);
-        },
-        0x4 => { // pop pop & setitem
-            let v = ds.pop().unwrap();
-            let k = ds.pop().unwrap();
-            ds.last_mut().unwrap().as_object().insert(k.into_string(), v);
+// Places a just-completed value into whatever is enclosing it: the list or
+// object on top of `stack`, or `result` if we're back at the top level.
+fn complete_value(stack: &mut Vec<Value>, keys: &mut Vec<Option<String>>, value: Value,
+                   result: &mut Option<Value>) -> Result<(), JsonError> {
+    match stack.last_mut() {
+        Some(&mut Value::List(ref mut l)) => {
+            l.push(value);
         },
-        0x5 => { // push null
-            ds.push(Value::Null);
-        },
-        0x6 => { // push true
-            ds.push(Value::Bool(true));
-        },
-        0x7 => { // push false
-            ds.push(Value::Bool(false));
-        },
-        0x8 => { // push string
-            ds.push(Value::String(ss.clone()));
-            ss.clear();
-            es.clear();
-        },
-        0x9 => { // push int
-            ds.push(Value::Int(ss.parse().unwrap()));
-            ss.clear();
-        },
-        0xA => { // push float
-            ds.push(Value::Float(ss.parse().unwrap()));
-            ss.clear();
-        },
-        0xB => { // push ch to ss
-            ss.push(ch as char);
-        },
-        0xC => { // push ch to es
-            es.push(ch as char);
-        }
-        0xD => { // push escape
-            let c: u8 = match ch as char {
-                'b' => 8,
-                't' => 9,
-                'n' => 10,
-                'f' => 12,
-                'r' => 13,
-                _ => { return Err(JsonError::InvalidEscape(format!("\\{}", ch))); },
-            };
-            ss.push(c as char);
-            es.clear();
+        Some(&mut Value::Object(ref mut o)) => {
+            let k = keys.last_mut().unwrap().take().unwrap();
+            o.insert(k, value);
         },
-        0xE => { // push unicode code point
-            let n = try!(u16::from_str_radix(es, 16).map_err(|_|
-                    JsonError::InvalidEscape(format!("\\u{}", es))));
-            if let Some(u) = char::from_u32(n as u32) {
-                ss.push(u);
-            } else {
-                return Err(JsonError::InvalidEscape(format!("\\u{}", es)));
+        Some(_) => unreachable!(),
+        None => {
+            if result.is_some() {
+                return Err(JsonError::MultipleObjects);
             }
-            es.clear();
+            *result = Some(value);
         },
-        _ => panic!("JSON decoder bug"),
     }
     Ok(())
 }
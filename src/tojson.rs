@@ -0,0 +1,276 @@
+use std::collections::BTreeMap;
+use std::ops::Index;
+
+use super::Value;
+
+/// Converts a Rust value into a `Value`, for building documents without
+/// constructing `Value` variants by hand.
+pub trait ToJson {
+    fn to_json(&self) -> Value;
+}
+
+macro_rules! int_to_json {
+    ($($ty:ty),*) => {
+        $(
+            impl ToJson for $ty {
+                fn to_json(&self) -> Value {
+                    Value::Int(*self as i64)
+                }
+            }
+        )*
+    }
+}
+
+macro_rules! uint_to_json {
+    ($($ty:ty),*) => {
+        $(
+            impl ToJson for $ty {
+                fn to_json(&self) -> Value {
+                    Value::Uint(*self as u64)
+                }
+            }
+        )*
+    }
+}
+
+int_to_json!(i8, i16, i32, i64, isize);
+uint_to_json!(u8, u16, u32, u64, usize);
+
+impl ToJson for bool {
+    fn to_json(&self) -> Value {
+        Value::Bool(*self)
+    }
+}
+
+impl ToJson for f32 {
+    fn to_json(&self) -> Value {
+        Value::Float(*self as f64)
+    }
+}
+
+impl ToJson for f64 {
+    fn to_json(&self) -> Value {
+        Value::Float(*self)
+    }
+}
+
+impl ToJson for String {
+    fn to_json(&self) -> Value {
+        Value::String(self.clone())
+    }
+}
+
+impl<'a> ToJson for &'a str {
+    fn to_json(&self) -> Value {
+        Value::String(self.to_string())
+    }
+}
+
+impl<T: ToJson> ToJson for Vec<T> {
+    fn to_json(&self) -> Value {
+        Value::List(self.iter().map(ToJson::to_json).collect())
+    }
+}
+
+impl<T: ToJson> ToJson for Option<T> {
+    fn to_json(&self) -> Value {
+        match *self {
+            Some(ref v) => v.to_json(),
+            None => Value::Null,
+        }
+    }
+}
+
+impl<T: ToJson> ToJson for BTreeMap<String, T> {
+    fn to_json(&self) -> Value {
+        Value::Object(self.iter().map(|(k, v)| (k.clone(), v.to_json())).collect())
+    }
+}
+
+impl Value {
+    /// Returns the string, or `None` if this isn't a `Value::String`.
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            Value::String(ref s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Returns the value as an `i64`, or `None` if it isn't an integer that
+    /// fits in one. A `Float` gives `None`, even when it is integral.
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Value::Int(n) => Some(n),
+            Value::Uint(n) if n <= ::std::i64::MAX as u64 => Some(n as i64),
+            _ => None,
+        }
+    }
+
+    /// Returns the value as a `u64`, or `None` if it isn't a non-negative
+    /// integer that fits in one. A `Float` gives `None`, even when it is
+    /// integral.
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            Value::Uint(n) => Some(n),
+            Value::Int(n) if n >= 0 => Some(n as u64),
+            _ => None,
+        }
+    }
+
+    /// Returns the value as an `f64`, or `None` if it isn't a number.
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Value::Float(f) => Some(f),
+            Value::Int(n) => Some(n as f64),
+            Value::Uint(n) => Some(n as f64),
+            _ => None,
+        }
+    }
+
+    /// Returns the bool, or `None` if this isn't a `Value::Bool`.
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            Value::Bool(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    /// Returns the list, or `None` if this isn't a `Value::List`.
+    pub fn as_list(&self) -> Option<&Vec<Value>> {
+        match *self {
+            Value::List(ref l) => Some(l),
+            _ => None,
+        }
+    }
+
+    /// Returns the object, or `None` if this isn't a `Value::Object`.
+    pub fn as_object(&self) -> Option<&BTreeMap<String, Value>> {
+        match *self {
+            Value::Object(ref o) => Some(o),
+            _ => None,
+        }
+    }
+
+    /// True if this is `Value::Null`.
+    pub fn is_null(&self) -> bool {
+        match *self {
+            Value::Null => true,
+            _ => false,
+        }
+    }
+
+    /// Looks up `key` in this value if it's an object, returning `None` if
+    /// it isn't an object or has no such key.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match *self {
+            Value::Object(ref o) => o.get(key),
+            _ => None,
+        }
+    }
+}
+
+/// Indexes into a `Value::List`. Returns `&Value::Null` rather than
+/// panicking if this isn't a list or the index is out of bounds.
+impl Index<usize> for Value {
+    type Output = Value;
+
+    fn index(&self, i: usize) -> &Value {
+        static NULL: Value = Value::Null;
+        match *self {
+            Value::List(ref l) => l.get(i).unwrap_or(&NULL),
+            _ => &NULL,
+        }
+    }
+}
+
+/// Indexes into a `Value::Object`. Returns `&Value::Null` rather than
+/// panicking if this isn't an object or has no such key.
+impl<'a> Index<&'a str> for Value {
+    type Output = Value;
+
+    fn index(&self, key: &'a str) -> &Value {
+        static NULL: Value = Value::Null;
+        match *self {
+            Value::Object(ref o) => o.get(key).unwrap_or(&NULL),
+            _ => &NULL,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Value;
+    use super::ToJson;
+
+    #[test]
+    fn to_json_covers_primitive_types() {
+        match 5i32.to_json() {
+            Value::Int(5) => {},
+            other => panic!("expected Int(5), got {:?}", other),
+        }
+        match 5u32.to_json() {
+            Value::Uint(5) => {},
+            other => panic!("expected Uint(5), got {:?}", other),
+        }
+        match 1.5f64.to_json() {
+            Value::Float(f) => assert_eq!(f, 1.5),
+            other => panic!("expected Float(1.5), got {:?}", other),
+        }
+        match "hi".to_json() {
+            Value::String(ref s) => assert_eq!(s, "hi"),
+            other => panic!("expected String, got {:?}", other),
+        }
+        match None::<i32>.to_json() {
+            Value::Null => {},
+            other => panic!("expected Null, got {:?}", other),
+        }
+        match Some(5i32).to_json() {
+            Value::Int(5) => {},
+            other => panic!("expected Int(5), got {:?}", other),
+        }
+        match vec![1i32, 2, 3].to_json() {
+            Value::List(ref l) => assert_eq!(l.len(), 3),
+            other => panic!("expected List, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn accessors_return_none_on_type_mismatch() {
+        let v = Value::String("not a number".to_string());
+        assert_eq!(v.as_i64(), None);
+        assert_eq!(v.as_u64(), None);
+        assert_eq!(v.as_f64(), None);
+        assert_eq!(v.as_bool(), None);
+        assert!(v.as_list().is_none());
+        assert!(v.as_object().is_none());
+        assert_eq!(Value::Int(5).as_str(), None);
+    }
+
+    #[test]
+    fn as_i64_and_as_u64_cross_convert_in_range() {
+        assert_eq!(Value::Uint(5).as_i64(), Some(5));
+        assert_eq!(Value::Int(5).as_u64(), Some(5));
+        assert_eq!(Value::Int(-1).as_u64(), None);
+        assert_eq!(Value::Uint(::std::u64::MAX).as_i64(), None);
+    }
+
+    #[test]
+    fn index_returns_null_instead_of_panicking() {
+        let list = Value::List(vec![Value::Int(1)]);
+        assert!(list[5].is_null());
+        assert!(list["key"].is_null());
+
+        let not_a_container = Value::Int(1);
+        assert!(not_a_container[0].is_null());
+        assert!(not_a_container["key"].is_null());
+    }
+
+    #[test]
+    fn get_returns_none_for_missing_key_or_non_object() {
+        let obj = Value::Object(
+            vec![("a".to_string(), Value::Int(1))].into_iter().collect());
+        assert!(obj.get("a").is_some());
+        assert!(obj.get("b").is_none());
+        assert!(Value::Int(1).get("a").is_none());
+    }
+}